@@ -118,12 +118,23 @@ fn start_midi(controller: SynthController) -> anyhow::Result<midir::MidiInputCon
 		println!("port: {}", midi_in.port_name(port)?);
 	}
 
+	// midir hands us a monotonic wall-clock timestamp in microseconds; anchor it to
+	// the audio clock on the first event so later events map to future frame offsets.
+	let mut clock_anchor: Option<(u64, u64)> = None;
+
 	midi_in.connect(
 		&ports[0],
 		"tunebox-input",
-		move |_stamp, message_raw, _| {
+		move |stamp, message_raw, _| {
 			if let Ok((msg, _)) = midi_msg::MidiMsg::from_midi(&message_raw) {
-				process_midi_event(msg, &controller);
+				let now_sample = controller.sample_clock();
+				let (anchor_stamp, anchor_sample) = *clock_anchor.get_or_insert((stamp, now_sample));
+
+				let elapsed_us = stamp.saturating_sub(anchor_stamp);
+				let target_sample = anchor_sample + elapsed_us * controller.sample_rate() as u64 / 1_000_000;
+				let sample_offset = target_sample.saturating_sub(now_sample).min(u32::MAX as u64) as u32;
+
+				process_midi_event(msg, &controller, sample_offset);
 			}
 		},
 		()
@@ -131,24 +142,42 @@ fn start_midi(controller: SynthController) -> anyhow::Result<midir::MidiInputCon
 	.map_err(Into::into)
 }
 
-fn process_midi_event(msg: midi_msg::MidiMsg, controller: &SynthController) {
+fn process_midi_event(msg: midi_msg::MidiMsg, controller: &SynthController, sample_offset: u32) {
 	use midi_msg::*;
 
 	match msg {
 		MidiMsg::ChannelVoice{ msg: ChannelVoiceMsg::NoteOn{note, velocity: 0}, .. }
 		| MidiMsg::ChannelVoice{ msg: ChannelVoiceMsg::NoteOff{note, ..}, .. }
 		=> {
-			controller.note_off(note);
+			controller.note_off_at(note, sample_offset);
 		}
 
 		MidiMsg::ChannelVoice{ msg: ChannelVoiceMsg::NoteOn{note, velocity}, .. } => {
-			controller.note_on(note, velocity);
+			controller.note_on_at(note, velocity, sample_offset);
+		}
+
+		MidiMsg::ChannelVoice{ msg: ChannelVoiceMsg::PitchBend{bend}, .. } => {
+			// 14-bit value centred at 0x2000, mapped to a symmetric semitone range.
+			let normalized = (bend as i32 - 0x2000) as f32 / 0x2000 as f32;
+			controller.pitch_bend(normalized * PITCH_BEND_SEMITONES);
+		}
+
+		MidiMsg::ChannelVoice{ msg: ChannelVoiceMsg::ControlChange{control}, .. } => {
+			match control {
+				ControlChange::ModWheel(value) => controller.control_change(1, value),
+				ControlChange::Volume(value) => controller.control_change(7, value),
+				ControlChange::Expression(value) => controller.control_change(11, value),
+				_ => {}
+			}
 		}
 
 		_ => {}
 	}
 }
 
+// Semitone range reached at full pitch-bend deflection in either direction.
+const PITCH_BEND_SEMITONES: f32 = 2.0;
+
 
 fn midi_to_pitch_class_octave(midi: i32) -> (PitchClass, i32) {
 	(PitchClass::from_midi(midi), midi/12 - 1)
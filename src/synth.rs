@@ -7,23 +7,90 @@ mod adsr;
 mod provider;
 use provider::*;
 
-pub use provider::{UiFeedback, UiVoice};
+pub use provider::{UiFeedback, UiVoice, Waveform, FilterMode, Sample, Tuning, Scale};
 
 
 #[derive(Clone)]
 pub struct SynthController {
 	msg_tx: SyncSender<SynthMessage>,
 
+	clock: Arc<SharedClock>,
+
 	ui_feedback: Option<Arc<Mutex<UiFeedback>>>,
 }
 
 impl SynthController {
 	pub fn note_on(&self, note: u8, velocity: u8) {
-		self.send(SynthMessage::NoteOn{note, velocity});
+		self.note_on_at(note, velocity, 0);
 	}
 
 	pub fn note_off(&self, note: u8) {
-		self.send(SynthMessage::NoteOff(note));
+		self.note_off_at(note, 0);
+	}
+
+	pub fn set_waveform(&self, waveform: Waveform) {
+		self.send(SynthMessage::SetWaveform(waveform));
+	}
+
+	pub fn set_filter(&self, cutoff: f32, resonance: f32, env_amount: f32, mode: FilterMode) {
+		self.send(SynthMessage::SetFilter{cutoff, resonance, env_amount, mode});
+	}
+
+	pub fn set_max_polyphony(&self, limit: usize) {
+		self.send(SynthMessage::SetMaxPolyphony(limit));
+	}
+
+	pub fn set_delay_time(&self, left: f32, right: f32) {
+		self.send(SynthMessage::SetDelayTime{left, right});
+	}
+
+	pub fn set_delay_feedback(&self, feedback: f32) {
+		self.send(SynthMessage::SetDelayFeedback(feedback));
+	}
+
+	pub fn set_delay_cross_feed(&self, amount: f32) {
+		self.send(SynthMessage::SetDelayCrossFeed(amount));
+	}
+
+	pub fn set_delay_wet(&self, wet: f32) {
+		self.send(SynthMessage::SetDelayWet(wet));
+	}
+
+	pub fn pitch_bend(&self, semitones: f32) {
+		self.send(SynthMessage::PitchBend(semitones));
+	}
+
+	pub fn control_change(&self, cc: u8, value: u8) {
+		self.send(SynthMessage::ControlChange{cc, value});
+	}
+
+	pub fn load_sample(&self, sample: Sample) {
+		self.send(SynthMessage::LoadSample(Arc::new(sample)));
+	}
+
+	pub fn set_tuning(&self, tuning: Tuning) {
+		self.send(SynthMessage::SetTuning(tuning));
+	}
+
+	pub fn set_scale(&self, scale: Option<Scale>) {
+		self.send(SynthMessage::SetScale(scale));
+	}
+
+	pub fn note_on_at(&self, note: u8, velocity: u8, sample_offset: u32) {
+		self.send(SynthMessage::NoteOn{note, velocity, sample_offset});
+	}
+
+	pub fn note_off_at(&self, note: u8, sample_offset: u32) {
+		self.send(SynthMessage::NoteOff{note, sample_offset});
+	}
+
+	// Samples rendered so far, for timestamping incoming MIDI against the audio clock.
+	pub fn sample_clock(&self) -> u64 {
+		self.clock.samples()
+	}
+
+	pub fn sample_rate(&self) -> u32 {
+		self.clock.sample_rate()
 	}
 
 	pub fn enable_ui_feedback(&mut self) {
@@ -48,9 +115,11 @@ impl SynthController {
 
 pub fn init_synth(audio: &mut audio::System) -> anyhow::Result<SynthController> {
 	let (msg_tx, msg_rx) = sync_channel(128);
-	audio.set_provider(SynthProvider::new(msg_rx))?;
+	let clock = Arc::new(SharedClock::new(44100));
+	audio.set_provider(SynthProvider::new(msg_rx, Arc::clone(&clock)))?;
 	Ok(SynthController {
 		msg_tx,
+		clock,
 		ui_feedback: None,
 	})
 }
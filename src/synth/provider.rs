@@ -1,6 +1,7 @@
 use toybox::prelude::*;
 
 use std::sync::mpsc::Receiver;
+use std::sync::atomic::{AtomicU64, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 
 use super::adsr::Adsr;
@@ -11,13 +12,107 @@ pub enum SynthMessage {
 	NoteOn {
 		note: u8,
 		velocity: u8,
+		sample_offset: u32,
 	},
 
-	NoteOff(u8),
+	NoteOff {
+		note: u8,
+		sample_offset: u32,
+	},
+
+	SetWaveform(Waveform),
+
+	SetFilter {
+		cutoff: f32,
+		resonance: f32,
+		env_amount: f32,
+		mode: FilterMode,
+	},
+
+	SetMaxPolyphony(usize),
+
+	SetDelayTime { left: f32, right: f32 },
+	SetDelayFeedback(f32),
+	SetDelayCrossFeed(f32),
+	SetDelayWet(f32),
+
+	PitchBend(f32),
+	ControlChange { cc: u8, value: u8 },
+
+	LoadSample(Arc<Sample>),
+
+	SetTuning(Tuning),
+	SetScale(Option<Scale>),
 
 	SetUiFeedback(Option<Arc<Mutex<UiFeedback>>>),
 }
 
+
+// A decoded PCM sample (mono or interleaved stereo) with the MIDI note it was
+// recorded at, so it can be repitched across the keyboard. An optional loop region
+// (in frames) sustains the sound for as long as the note is held.
+pub struct Sample {
+	data: Vec<f32>,
+	channels: usize,
+	root_note: u8,
+	loop_region: Option<(usize, usize)>,
+}
+
+impl Sample {
+	pub fn new(data: Vec<f32>, channels: usize, root_note: u8) -> Sample {
+		Sample {
+			data,
+			channels,
+			root_note,
+			loop_region: None,
+		}
+	}
+
+	pub fn with_loop(mut self, start: usize, end: usize) -> Sample {
+		self.loop_region = Some((start, end));
+		self
+	}
+
+	fn frame_count(&self) -> usize {
+		if self.channels == 0 {
+			0
+		} else {
+			self.data.len() / self.channels
+		}
+	}
+
+	fn frame(&self, index: usize) -> [f32; 2] {
+		match self.channels {
+			1 => {
+				let s = self.data[index];
+				[s, s]
+			}
+			_ => [self.data[index * self.channels], self.data[index * self.channels + 1]],
+		}
+	}
+}
+
+
+// Oscillator shape for each voice. `Square`'s duty cycle mirrors the Game Boy
+// pulse channels, and `Noise` is driven by the per-voice LFSR rather than the phase.
+#[derive(Copy, Clone, Debug)]
+pub enum Waveform {
+	Sine,
+	Square { duty: f32 },
+	Saw,
+	Triangle,
+	Noise,
+}
+
+
+// Which of the state-variable filter's simultaneous outputs a voice taps.
+#[derive(Copy, Clone, Debug)]
+pub enum FilterMode {
+	Low,
+	Band,
+	High,
+}
+
 #[derive(Debug)]
 pub struct UiFeedback {
 	pub voices: Vec<UiVoice>,
@@ -32,25 +127,129 @@ pub struct UiVoice {
 }
 
 
+// Running audio clock shared with the `SynthController` so incoming MIDI can be
+// timestamped against the samples already rendered. The provider owns the
+// authoritative counter and publishes the buffer-start position here each buffer.
+pub(crate) struct SharedClock {
+	samples: AtomicU64,
+	sample_rate: AtomicU32,
+}
+
+impl SharedClock {
+	pub fn new(sample_rate: u32) -> SharedClock {
+		SharedClock {
+			samples: AtomicU64::new(0),
+			sample_rate: AtomicU32::new(sample_rate),
+		}
+	}
+
+	pub fn samples(&self) -> u64 {
+		self.samples.load(Ordering::Relaxed)
+	}
+
+	pub fn sample_rate(&self) -> u32 {
+		self.sample_rate.load(Ordering::Relaxed)
+	}
+
+	fn publish(&self, samples: u64) {
+		self.samples.store(samples, Ordering::Relaxed);
+	}
+
+	fn set_sample_rate(&self, sample_rate: u32) {
+		self.sample_rate.store(sample_rate, Ordering::Relaxed);
+	}
+}
+
+
+// A note event queued for a specific frame within the current (or a following)
+// buffer. Offsets are always relative to the start of the buffer they live in;
+// anything still pending past the buffer end is carried over with its offset
+// rebased by the buffer length.
+struct ScheduledEvent {
+	sample_offset: u32,
+	event: NoteEvent,
+}
+
+enum NoteEvent {
+	On { note: u8, velocity: u8 },
+	Off { note: u8 },
+}
+
+
+// Full-scale vibrato depth (in semitones) reached at a fully-raised mod wheel.
+const VIBRATO_MAX_SEMITONES: f32 = 1.0;
+
+// Global performance modulation applied per-frame to every voice: a pitch-bend
+// frequency multiplier, a shared vibrato LFO, and a master gain driven by CC7/CC11.
+#[derive(Copy, Clone)]
+struct Modulation {
+	pitch_multiplier: f32,
+	vibrato_depth: f32,
+	master_gain: f32,
+
+	lfo_hz: f32,
+	lfo_phase: f32,
+}
+
+impl Modulation {
+	fn new() -> Modulation {
+		Modulation {
+			pitch_multiplier: 1.0,
+			vibrato_depth: 0.0,
+			master_gain: 1.0,
+
+			lfo_hz: 5.5,
+			lfo_phase: 0.0,
+		}
+	}
+}
+
+
 pub struct SynthProvider {
 	msg_rx: Receiver<SynthMessage>,
 	voice_bank: VoiceBank,
 
 	ui_feedback: Option<Arc<Mutex<UiFeedback>>>,
 
+	clock: Arc<SharedClock>,
+	sample_clock: u64,
+	scheduled: Vec<ScheduledEvent>,
+
+	delay: StereoDelay,
+
+	modulation: Modulation,
+	cc_volume: f32,
+	cc_expression: f32,
+
+	tuning: Tuning,
+
 	sample_rate: u32,
 	channels: usize,
 }
 
 impl SynthProvider {
-	pub fn new(msg_rx: Receiver<SynthMessage>) -> Self {
+	pub fn new(msg_rx: Receiver<SynthMessage>, clock: Arc<SharedClock>) -> Self {
+		let sample_rate = clock.sample_rate();
+
 		SynthProvider {
 			msg_rx,
 			voice_bank: VoiceBank::new(),
 
 			ui_feedback: None,
 
-			sample_rate: 44100,
+			clock,
+			sample_clock: 0,
+			scheduled: Vec::with_capacity(64),
+
+			delay: StereoDelay::new(sample_rate),
+
+			modulation: Modulation::new(),
+			cc_volume: 1.0,
+			cc_expression: 1.0,
+
+			tuning: Tuning::default(),
+
+			sample_rate,
 			channels: 2,
 		}
 	}
@@ -60,14 +259,52 @@ impl SynthProvider {
 
 		for msg in self.msg_rx.try_iter() {
 			match msg {
-				NoteOff(note) => self.voice_bank.note_off(note),
-				NoteOn{note, velocity} => self.voice_bank.note_on(note, velocity),
+				NoteOff{note, sample_offset} => self.scheduled.push(ScheduledEvent {
+					sample_offset,
+					event: NoteEvent::Off{note},
+				}),
+
+				NoteOn{note, velocity, sample_offset} => self.scheduled.push(ScheduledEvent {
+					sample_offset,
+					event: NoteEvent::On{note, velocity},
+				}),
+
+				SetWaveform(waveform) => self.voice_bank.set_waveform(waveform),
+
+				SetFilter{cutoff, resonance, env_amount, mode} =>
+					self.voice_bank.set_filter(cutoff, resonance, env_amount, mode),
+
+				SetMaxPolyphony(limit) => self.voice_bank.max_polyphony = limit.max(1),
+
+				SetDelayTime{left, right} => self.delay.set_times(left, right),
+				SetDelayFeedback(feedback) => self.delay.feedback = feedback.clamp(0.0, 0.99),
+				SetDelayCrossFeed(amount) => self.delay.cross_feed = amount.clamp(0.0, 1.0),
+				SetDelayWet(wet) => self.delay.wet = wet.clamp(0.0, 1.0),
+
+				PitchBend(semitones) => self.modulation.pitch_multiplier = (semitones / 12.0).exp2(),
+				ControlChange{cc, value} => {
+					let amount = value.min(127) as f32 / 127.0;
+					match cc {
+						1 => self.modulation.vibrato_depth = amount * VIBRATO_MAX_SEMITONES,
+						7 => self.cc_volume = amount,
+						11 => self.cc_expression = amount,
+						_ => {}
+					}
+					self.modulation.master_gain = self.cc_volume * self.cc_expression;
+				}
+
+				LoadSample(sample) => self.voice_bank.sample = Some(sample),
+
+				SetTuning(tuning) => self.tuning = tuning,
+				SetScale(scale) => self.voice_bank.scale = scale,
 
 				SetUiFeedback(ui_feedback) => {
 					self.ui_feedback = ui_feedback;
 				}
 			}
 		}
+
+		self.scheduled.sort_by_key(|e| e.sample_offset);
 	}
 
 	fn collect_feedback(&self) {
@@ -86,6 +323,15 @@ impl SynthProvider {
 			});
 		}
 
+		for voice in self.voice_bank.sample_voices.iter() {
+			ui_feedback.voices.push(UiVoice {
+				envelope: voice.adsr.value(),
+				pan: voice.pan * 2.0 - 1.0,
+				note: voice.note,
+				active: voice.active,
+			});
+		}
+
 		ui_feedback.voices.sort_by_key(|v| v.note);
 	}
 }
@@ -94,11 +340,11 @@ impl audio::Provider for SynthProvider {
 	fn on_configuration_changed(&mut self, audio::Configuration{sample_rate, channels}: audio::Configuration) {
 		self.sample_rate = sample_rate;
 		self.channels = channels;
+		self.clock.set_sample_rate(sample_rate);
+		self.delay.configure(sample_rate);
 	}
 
 	fn fill_buffer(&mut self, buffer: &mut [f32]) {
-		self.process_messages();
-
 		assert!(self.channels == 2);
 
 		buffer.fill(0.0);
@@ -106,11 +352,63 @@ impl audio::Provider for SynthProvider {
 		let sample_dt = (self.sample_rate as f32).recip();
 
 		let (buffer_stereo, _) = buffer.as_chunks_mut();
+		let frames = buffer_stereo.len();
+
+		// Publish the buffer-start position before draining so that MIDI arriving
+		// during this buffer is timestamped against a stable reference.
+		self.clock.publish(self.sample_clock);
+		self.process_messages();
+
+		// Walk the buffer frame-by-frame, applying each scheduled event exactly at
+		// its frame and filling the stretch of output between consecutive events.
+		let mut cursor = 0;
+		let mut frame = 0;
+
+		while frame < frames {
+			while cursor < self.scheduled.len() && (self.scheduled[cursor].sample_offset as usize) <= frame {
+				match self.scheduled[cursor].event {
+					NoteEvent::On{note, velocity} => self.voice_bank.note_on(note, velocity, &self.tuning),
+					NoteEvent::Off{note} => self.voice_bank.note_off(note),
+				}
+				cursor += 1;
+			}
 
-		for voice in self.voice_bank.voices.iter_mut() {
-			voice.update_and_fill(buffer_stereo, sample_dt);
+			let next = self.scheduled.get(cursor)
+				.map_or(frames, |e| (e.sample_offset as usize).min(frames));
+
+			// Advance a copy of the shared LFO to this segment's start so every voice
+			// in the segment reads an identical, phase-consistent vibrato.
+			let mut segment_mod = self.modulation;
+			segment_mod.lfo_phase += TAU * self.modulation.lfo_hz * sample_dt * frame as f32;
+
+			for voice in self.voice_bank.voices.iter_mut() {
+				voice.update_and_fill(&mut buffer_stereo[frame..next], sample_dt, &segment_mod, &self.tuning);
+			}
+
+			for voice in self.voice_bank.sample_voices.iter_mut() {
+				voice.update_and_fill(&mut buffer_stereo[frame..next], sample_dt, &segment_mod);
+			}
+
+			frame = next;
+		}
+
+		// Keep the master LFO running continuously across buffers.
+		self.modulation.lfo_phase =
+			(self.modulation.lfo_phase + TAU * self.modulation.lfo_hz * sample_dt * frames as f32) % TAU;
+
+		// Master effects bus: the whole voice mix passes through the stereo delay once.
+		self.delay.process(buffer_stereo);
+
+		// Drop everything already applied and carry any remaining future events
+		// into the next buffer, rebasing their offsets past this buffer's end.
+		let frames = frames as u32;
+		self.scheduled.drain(..cursor);
+		for event in self.scheduled.iter_mut() {
+			event.sample_offset = event.sample_offset.saturating_sub(frames);
 		}
 
+		self.sample_clock += frames as u64;
+
 		self.voice_bank.clean_up();
 		self.collect_feedback();
 	}
@@ -121,47 +419,174 @@ impl audio::Provider for SynthProvider {
 
 struct VoiceBank {
 	voices: Vec<Voice>,
+	sample_voices: Vec<SampleVoice>,
+
+	sample: Option<Arc<Sample>>,
+	scale: Option<Scale>,
 
 	pan_seed: f32,
+	waveform: Waveform,
+
+	cutoff: f32,
+	resonance: f32,
+	env_amount: f32,
+	filter_mode: FilterMode,
+
+	max_polyphony: usize,
+	next_age: u64,
 }
 
+const DEFAULT_MAX_POLYPHONY: usize = 16;
+
 impl VoiceBank {
 	fn new() -> Self {
 		VoiceBank {
 			voices: Vec::with_capacity(32),
+			sample_voices: Vec::with_capacity(32),
+
+			sample: None,
+			scale: None,
+
 			pan_seed: 0.0,
+			waveform: Waveform::Sine,
+
+			cutoff: 300.0,
+			resonance: 3.0,
+			env_amount: 6000.0,
+			filter_mode: FilterMode::Low,
+
+			max_polyphony: DEFAULT_MAX_POLYPHONY,
+			next_age: 0,
 		}
 	}
 
-	fn note_off(&mut self, note: u8) {
+	fn set_waveform(&mut self, waveform: Waveform) {
+		self.waveform = waveform;
+
 		for voice in self.voices.iter_mut() {
-			if voice.note == note {
-				voice.release();
-				break
-			}
+			voice.waveform = waveform;
+		}
+	}
+
+	fn set_filter(&mut self, cutoff: f32, resonance: f32, env_amount: f32, mode: FilterMode) {
+		self.cutoff = cutoff;
+		self.resonance = resonance;
+		self.env_amount = env_amount;
+		self.filter_mode = mode;
+
+		for voice in self.voices.iter_mut() {
+			voice.set_filter(cutoff, resonance, env_amount, mode);
+		}
+	}
+
+	fn note_off(&mut self, note: u8) {
+		if let Some(voice) = self.voices.iter_mut().find(|v| v.note == note) {
+			voice.release();
+		}
+
+		if let Some(voice) = self.sample_voices.iter_mut().find(|v| v.note == note) {
+			voice.release();
 		}
 	}
 
-	fn note_on(&mut self, note: u8, velocity: u8) {
+	fn note_on(&mut self, note: u8, velocity: u8, tuning: &Tuning) {
+		// Snap to the selected scale before anything else, so the key constraint
+		// applies uniformly to oscillator and sampler voices.
+		let note = self.scale.as_ref().map_or(note, |scale| scale.quantize(note));
+
 		let gain = midi_velocity_to_gain(velocity) * 0.7;
 
+		let pan = (self.pan_seed - 0.5) * 1.2;
+		self.pan_seed = (self.pan_seed + 2503.0 / 443.0).fract();
+
+		// With a sample loaded we play the sampler voice; otherwise the oscillator.
+		if let Some(sample) = self.sample.clone() {
+			if let Some(voice) = self.sample_voices.iter_mut().find(|v| v.note == note) {
+				voice.restart(gain);
+				return;
+			}
+
+			let age = self.next_age;
+			self.next_age += 1;
+
+			let ratio = tuning.note_to_freq(note) / tuning.note_to_freq(sample.root_note);
+			let voice = SampleVoice::new(sample, note, ratio, gain, pan, age);
+
+			if self.sample_voices.len() >= self.max_polyphony {
+				if let Some(idx) = pick_steal_victim(&self.sample_voices) {
+					self.sample_voices[idx] = voice;
+				}
+			} else {
+				self.sample_voices.push(voice);
+			}
+
+			return;
+		}
+
 		if let Some(voice) = self.voices.iter_mut().find(|v| v.note == note) {
 			voice.restart(gain);
-		} else {
-			let pan = (self.pan_seed - 0.5) * 1.2;
+			return;
+		}
 
-			self.pan_seed = (self.pan_seed + 2503.0 / 443.0).fract();
+		let age = self.next_age;
+		self.next_age += 1;
 
-			self.voices.push(Voice::new(note, gain, pan));
+		let mut voice = Voice::new(note, gain, pan, self.cutoff, self.resonance, self.env_amount);
+		voice.waveform = self.waveform;
+		voice.filter_mode = self.filter_mode;
+		voice.age = age;
+
+		// Re-init the stolen voice in place so its filter/envelope restart from zero.
+		// The stolen note's output stops at once, without a release or fade-out.
+		if self.voices.len() >= self.max_polyphony {
+			if let Some(idx) = pick_steal_victim(&self.voices) {
+				self.voices[idx] = voice;
+			}
+		} else {
+			self.voices.push(voice);
 		}
 	}
 
 	fn clean_up(&mut self) {
 		self.voices.retain(|v| !v.is_silent());
+		self.sample_voices.retain(|v| !v.is_silent());
 	}
 }
 
 
+// Voice-stealing candidacy, shared by oscillator and sampler voices.
+trait Stealable {
+	fn active(&self) -> bool;
+	fn envelope(&self) -> f32;
+	fn age(&self) -> u64;
+}
+
+// Prefer a voice already releasing, otherwise the one with the lowest current
+// envelope value; equal envelopes steal oldest-first via allocation age.
+fn pick_steal_victim<V: Stealable>(voices: &[V]) -> Option<usize> {
+	voices.iter()
+		.enumerate()
+		.min_by(|&(_, a), &(_, b)| {
+			(a.active() as u8).cmp(&(b.active() as u8))
+				.then(a.envelope().total_cmp(&b.envelope()))
+				.then(a.age().cmp(&b.age()))
+		})
+		.map(|(idx, _)| idx)
+}
+
+impl Stealable for Voice {
+	fn active(&self) -> bool { self.active }
+	fn envelope(&self) -> f32 { self.adsr.value() }
+	fn age(&self) -> u64 { self.age }
+}
+
+impl Stealable for SampleVoice {
+	fn active(&self) -> bool { self.active }
+	fn envelope(&self) -> f32 { self.adsr.value() }
+	fn age(&self) -> u64 { self.age }
+}
+
+
 
 struct Voice {
 	phase: f32,
@@ -172,10 +597,26 @@ struct Voice {
 	pan: f32,
 
 	note: u8,
+	age: u64,
+
+	filter: StateVariableFilter,
+	filter_adsr: Adsr,
+	filter_mode: FilterMode,
+	cutoff: f32,
+	env_amount: f32,
+
+	waveform: Waveform,
+
+	// 15-bit linear-feedback shift register state for the `Noise` waveform.
+	lfsr: u16,
+	noise_acc: f32,
+	noise_out: f32,
 }
 
 impl Voice {
-	fn new(note: u8, gain: f32, pan: f32) -> Voice {
+	// `cutoff` is the resting cutoff in Hz, `resonance` is the filter Q, and
+	// `env_amount` is how far (in Hz) the dedicated filter envelope opens the cutoff.
+	fn new(note: u8, gain: f32, pan: f32, cutoff: f32, resonance: f32, env_amount: f32) -> Voice {
 		Voice {
 			note,
 			adsr: Adsr::new(0.03, 0.2, 0.5, 4.0, gain),
@@ -184,17 +625,46 @@ impl Voice {
 			silence_timer: 0,
 
 			pan: (pan * 0.5 + 0.5).clamp(0.0, 1.0),
+			age: 0,
 
 			phase: 0.0,
+
+			filter: StateVariableFilter::new(resonance),
+			filter_adsr: Adsr::new(0.03, 0.4, 0.3, 1.5, 1.0),
+			filter_mode: FilterMode::Low,
+			cutoff,
+			env_amount,
+
+			waveform: Waveform::Sine,
+
+			lfsr: 0x7fff,
+			noise_acc: 0.0,
+			noise_out: 1.0,
 		}
 	}
 
+	// Advance the LFSR one step: feedback is the XOR of the low two bits, shifted
+	// into bit 14. (Also seeding bit 6 would give the shorter, more metallic period.)
+	fn clock_noise(&mut self) {
+		let feedback = (self.lfsr & 1) ^ ((self.lfsr >> 1) & 1);
+		self.lfsr >>= 1;
+		self.lfsr |= feedback << 14;
+		self.noise_out = if self.lfsr & 1 != 0 { -1.0 } else { 1.0 };
+	}
+
 	fn restart(&mut self, gain: f32) {
 		self.adsr.set_gain(gain);
 		self.active = true;
 		self.silence_timer = 0;
 	}
 
+	fn set_filter(&mut self, cutoff: f32, resonance: f32, env_amount: f32, mode: FilterMode) {
+		self.cutoff = cutoff;
+		self.env_amount = env_amount;
+		self.filter_mode = mode;
+		self.filter.set_resonance(resonance);
+	}
+
 	fn release(&mut self) {
 		self.active = false;
 	}
@@ -203,22 +673,61 @@ impl Voice {
 		self.silence_timer > 40
 	}
 
-	fn update_and_fill(&mut self, out: &mut [[f32; 2]], sample_dt: f32) {
-		let freq = midi_note_to_freq(self.note);
-		let inc = TAU * freq * sample_dt;
+	fn update_and_fill(&mut self, out: &mut [[f32; 2]], sample_dt: f32, modulation: &Modulation, tuning: &Tuning) {
+		let base_freq = tuning.note_to_freq(self.note);
 
 		let l_gain = (self.pan).sqrt();
 		let r_gain = (1.0 - self.pan).sqrt();
 
-		for [l_sample, r_sample] in out {
+		let nyquist = 0.5 / sample_dt;
+
+		for (i, [l_sample, r_sample]) in out.iter_mut().enumerate() {
 			let env = self.adsr.advance(sample_dt, self.active);
 
-			let osc = self.phase.sin() * 3.0
-				+ (self.phase * 3.0).sin() * 2.0
-				+ (self.phase * 5.0).sin() * 1.0;
-			let osc = osc / 6.0;
+			// Bend plus shared vibrato LFO combine into a per-frame frequency multiplier.
+			let lfo = (modulation.lfo_phase + TAU * modulation.lfo_hz * sample_dt * i as f32).sin();
+			let vibrato = (lfo * modulation.vibrato_depth / 12.0).exp2();
+			let freq = base_freq * modulation.pitch_multiplier * vibrato;
+			let inc = TAU * freq * sample_dt;
+
+			let osc = match self.waveform {
+				Waveform::Sine => self.phase.sin(),
+
+				Waveform::Square{duty} => {
+					let t = (self.phase / TAU).fract();
+					if t < duty { 1.0 } else { -1.0 }
+				}
+
+				Waveform::Saw => (self.phase / TAU).fract() * 2.0 - 1.0,
+
+				Waveform::Triangle => {
+					let t = (self.phase / TAU).fract();
+					2.0 * (2.0 * t - 1.0).abs() - 1.0
+				}
+
+				Waveform::Noise => {
+					self.noise_acc += freq * sample_dt;
+					while self.noise_acc >= 1.0 {
+						self.noise_acc -= 1.0;
+						self.clock_noise();
+					}
+					self.noise_out
+				}
+			};
+
+			// Sweep the cutoff with the filter envelope before recomputing g.
+			let filter_env = self.filter_adsr.advance(sample_dt, self.active);
+			let cutoff = (self.cutoff + self.env_amount * filter_env).clamp(20.0, nyquist * 0.99);
+			let g = (TAU * 0.5 * cutoff * sample_dt).tan();
+
+			let outputs = self.filter.evaluate(osc, g);
+			let filtered = match self.filter_mode {
+				FilterMode::Low => outputs.low,
+				FilterMode::Band => outputs.band,
+				FilterMode::High => outputs.high,
+			};
 
-			let sample = osc * env;
+			let sample = filtered * env * modulation.master_gain;
 
 			*l_sample += sample * l_gain;
 			*r_sample += sample * r_gain;
@@ -237,8 +746,228 @@ impl Voice {
 
 
 
-fn midi_note_to_freq(note: u8) -> f32 {
-    ((note as f32 - 69.0) / 12.0).exp2() * 440.0
+// A voice that plays a loaded `Sample` back through the same MIDI path as the
+// oscillator `Voice`, repitched by the ratio of the played note to the sample's
+// root note and shaped by its own `Adsr` and the shared pan law.
+struct SampleVoice {
+	sample: Arc<Sample>,
+
+	position: f32,
+	ratio: f32,
+
+	adsr: Adsr,
+	active: bool,
+	silence_timer: u8,
+	finished: bool,
+
+	pan: f32,
+
+	note: u8,
+	age: u64,
+}
+
+impl SampleVoice {
+	fn new(sample: Arc<Sample>, note: u8, ratio: f32, gain: f32, pan: f32, age: u64) -> SampleVoice {
+		SampleVoice {
+			sample,
+
+			position: 0.0,
+			ratio,
+
+			adsr: Adsr::new(0.03, 0.2, 0.5, 4.0, gain),
+			active: true,
+			silence_timer: 0,
+			finished: false,
+
+			pan: (pan * 0.5 + 0.5).clamp(0.0, 1.0),
+
+			note,
+			age,
+		}
+	}
+
+	fn restart(&mut self, gain: f32) {
+		self.adsr.set_gain(gain);
+		self.active = true;
+		self.silence_timer = 0;
+		self.finished = false;
+		self.position = 0.0;
+	}
+
+	fn release(&mut self) {
+		self.active = false;
+	}
+
+	fn is_silent(&self) -> bool {
+		self.finished || self.silence_timer > 40
+	}
+
+	fn update_and_fill(&mut self, out: &mut [[f32; 2]], sample_dt: f32, modulation: &Modulation) {
+		let frame_count = self.sample.frame_count();
+		if frame_count == 0 {
+			self.finished = true;
+			return;
+		}
+
+		let l_gain = (self.pan).sqrt();
+		let r_gain = (1.0 - self.pan).sqrt();
+
+		for (i, [l_sample, r_sample]) in out.iter_mut().enumerate() {
+			let env = self.adsr.advance(sample_dt, self.active);
+
+			if self.finished {
+				continue;
+			}
+
+			let lfo = (modulation.lfo_phase + TAU * modulation.lfo_hz * sample_dt * i as f32).sin();
+			let vibrato = (lfo * modulation.vibrato_depth / 12.0).exp2();
+			let rate = self.ratio * modulation.pitch_multiplier * vibrato;
+
+			// Linear interpolation between the two frames straddling the read head.
+			let index = self.position.floor() as usize;
+			let frac = self.position - index as f32;
+
+			let [l0, r0] = self.sample.frame(index.min(frame_count - 1));
+			let [l1, r1] = self.sample.frame((index + 1).min(frame_count - 1));
+
+			let sample_l = (l0 + (l1 - l0) * frac) * env * modulation.master_gain;
+			let sample_r = (r0 + (r1 - r0) * frac) * env * modulation.master_gain;
+
+			*l_sample += sample_l * l_gain;
+			*r_sample += sample_r * r_gain;
+
+			self.position += rate;
+
+			match self.sample.loop_region {
+				Some((start, end)) if self.position >= end as f32 => {
+					self.position = start as f32 + (self.position - end as f32);
+				}
+				_ if self.position >= frame_count as f32 => {
+					self.finished = true;
+				}
+				_ => {}
+			}
+		}
+
+		if self.adsr.is_silent() {
+			self.silence_timer = self.silence_timer.saturating_add(1);
+		}
+	}
+}
+
+
+// A frequency table mapping MIDI notes to pitches. The reference note is pinned to
+// `reference_freq` (a tunable A4), the table repeats every `octave_ratio`, and each
+// degree is placed by its offset in cents — so equal temperament, just intonation
+// and arbitrary N-EDO tunings are all just different `cents` tables.
+#[derive(Clone, Debug)]
+pub struct Tuning {
+	reference_note: u8,
+	reference_freq: f32,
+	octave_ratio: f32,
+	cents: Vec<f32>,
+}
+
+impl Tuning {
+	pub fn new(reference_note: u8, reference_freq: f32, octave_ratio: f32, cents: Vec<f32>) -> Tuning {
+		Tuning { reference_note, reference_freq, octave_ratio, cents }
+	}
+
+	// Standard 12-tone equal temperament with a tunable A4 reference.
+	pub fn equal_temperament(reference_freq: f32) -> Tuning {
+		Tuning::edo(12, reference_freq)
+	}
+
+	// N equal divisions of the octave.
+	pub fn edo(divisions: u32, reference_freq: f32) -> Tuning {
+		let step = 1200.0 / divisions as f32;
+		let cents = (0..divisions).map(|d| d as f32 * step).collect();
+		Tuning::new(69, reference_freq, 2.0, cents)
+	}
+
+	// Five-limit just intonation over twelve degrees, rooted at the reference note.
+	pub fn just_intonation(reference_freq: f32) -> Tuning {
+		let ratios = [
+			1.0/1.0, 16.0/15.0, 9.0/8.0, 6.0/5.0, 5.0/4.0, 4.0/3.0,
+			45.0/32.0, 3.0/2.0, 8.0/5.0, 5.0/3.0, 9.0/5.0, 15.0/8.0,
+		];
+		let cents = ratios.iter().map(|r: &f32| 1200.0 * r.log2()).collect();
+		Tuning::new(69, reference_freq, 2.0, cents)
+	}
+
+	pub fn note_to_freq(&self, note: u8) -> f32 {
+		let divisions = self.cents.len() as i32;
+		if divisions == 0 {
+			return self.reference_freq;
+		}
+
+		let rel = note as i32 - self.reference_note as i32;
+		let octave = rel.div_euclid(divisions);
+		let degree = rel.rem_euclid(divisions) as usize;
+
+		self.reference_freq * self.octave_ratio.powi(octave) * (self.cents[degree] / 1200.0).exp2()
+	}
+}
+
+impl Default for Tuning {
+	fn default() -> Tuning {
+		Tuning::equal_temperament(440.0)
+	}
+}
+
+
+// A musical scale expressed as semitone offsets from a root pitch class. Incoming
+// MIDI notes are snapped to the nearest allowed degree to constrain a keyboard to a key.
+#[derive(Clone, Debug)]
+pub struct Scale {
+	root: u8,
+	intervals: Vec<u8>,
+}
+
+impl Scale {
+	pub fn new(root: u8, intervals: Vec<u8>) -> Scale {
+		Scale { root, intervals }
+	}
+
+	pub fn ionian(root: u8) -> Scale {
+		Scale::new(root, vec![0, 2, 4, 5, 7, 9, 11])
+	}
+
+	pub fn dorian(root: u8) -> Scale {
+		Scale::new(root, vec![0, 2, 3, 5, 7, 9, 10])
+	}
+
+	pub fn pentatonic(root: u8) -> Scale {
+		Scale::new(root, vec![0, 2, 4, 7, 9])
+	}
+
+	fn quantize(&self, note: u8) -> u8 {
+		if self.intervals.is_empty() {
+			return note;
+		}
+
+		let n = note as i32;
+		let octave_base = n - (n - self.root as i32).rem_euclid(12);
+
+		let mut best = note;
+		let mut best_dist = i32::MAX;
+
+		// Check the matching degrees in the neighbouring octaves too so notes near an
+		// octave boundary can snap upward across it.
+		for &interval in self.intervals.iter() {
+			for octave in [-12, 0, 12] {
+				let candidate = octave_base + interval as i32 + octave;
+				let dist = (candidate - n).abs();
+
+				if (0..=127).contains(&candidate) && dist < best_dist {
+					best_dist = dist;
+					best = candidate as u8;
+				}
+			}
+		}
+
+		best
+	}
 }
 
 fn midi_velocity_to_gain(velocity: u8) -> f32 {
@@ -246,33 +975,131 @@ fn midi_velocity_to_gain(velocity: u8) -> f32 {
 }
 
 
-struct BasicLP {
-	freq: f32,
+// Topology-preserving (TPT / Zavalishin) state-variable filter. A single pass
+// yields simultaneous low/high/band outputs; `g = tan(PI * cutoff / sample_rate)`
+// is recomputed by the caller so the cutoff can be modulated per sample.
+struct StateVariableFilter {
+	k: f32,
 
-	alpha: f32,
-	prev_value: f32,
+	ic1eq: f32,
+	ic2eq: f32,
 }
 
-impl BasicLP {
-	fn new(freq: f32) -> BasicLP {
-		BasicLP {
-			freq,
+struct FilterOutputs {
+	low: f32,
+	high: f32,
+	band: f32,
+}
 
-			alpha: 0.0,
-			prev_value: 0.0,
+impl StateVariableFilter {
+	fn new(resonance: f32) -> StateVariableFilter {
+		StateVariableFilter {
+			k: resonance.max(0.0001).recip(),
+
+			ic1eq: 0.0,
+			ic2eq: 0.0,
 		}
 	}
 
-	fn set_sample_dt(&mut self, sample_dt: f32) {
-		self.alpha = Self::calc_alpha(self.freq, sample_dt);
+	fn set_resonance(&mut self, resonance: f32) {
+		self.k = resonance.max(0.0001).recip();
 	}
 
-	fn calc_alpha(freq: f32, dt: f32) ->  f32 {
-		dt / (dt + (TAU * freq).recip())
+	fn evaluate(&mut self, x: f32, g: f32) -> FilterOutputs {
+		let a1 = 1.0 / (1.0 + g * (g + self.k));
+		let v1 = a1 * (self.ic1eq + g * (x - self.ic2eq));
+		let v2 = self.ic2eq + g * v1;
+
+		self.ic1eq = 2.0 * v1 - self.ic1eq;
+		self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+		FilterOutputs {
+			low: v2,
+			band: v1,
+			high: x - self.k * v1 - v2,
+		}
 	}
+}
+
+
+// Longest delay time the ring buffers are sized to hold.
+const MAX_DELAY_SECONDS: f32 = 2.0;
 
-	fn evaluate(&mut self, value: f32) -> f32 {
-		self.prev_value = self.prev_value + (value - self.prev_value) * self.alpha;
-		self.prev_value
+// Stereo feedback delay run over the whole voice mix. A single write cursor walks
+// both ring buffers; each channel reads its own number of samples behind the cursor,
+// and `cross_feed` routes each delayed channel back into the other for a ping-pong.
+struct StereoDelay {
+	left: Vec<f32>,
+	right: Vec<f32>,
+	write: usize,
+
+	sample_rate: u32,
+	delay_l: usize,
+	delay_r: usize,
+	feedback: f32,
+	cross_feed: f32,
+	wet: f32,
+}
+
+impl StereoDelay {
+	fn new(sample_rate: u32) -> StereoDelay {
+		let capacity = (sample_rate as f32 * MAX_DELAY_SECONDS) as usize + 1;
+
+		let mut delay = StereoDelay {
+			left: vec![0.0; capacity],
+			right: vec![0.0; capacity],
+			write: 0,
+
+			sample_rate,
+			delay_l: 0,
+			delay_r: 0,
+			feedback: 0.35,
+			cross_feed: 0.5,
+			wet: 0.0,
+		};
+
+		delay.set_times(0.25, 0.375);
+		delay
+	}
+
+	fn configure(&mut self, sample_rate: u32) {
+		let capacity = (sample_rate as f32 * MAX_DELAY_SECONDS) as usize + 1;
+
+		self.left = vec![0.0; capacity];
+		self.right = vec![0.0; capacity];
+		self.write = 0;
+
+		let (left, right) = (self.delay_l, self.delay_r);
+		self.sample_rate = sample_rate;
+		self.set_times(left as f32 / sample_rate as f32, right as f32 / sample_rate as f32);
+	}
+
+	fn set_times(&mut self, left: f32, right: f32) {
+		let max = self.left.len().saturating_sub(1);
+		self.delay_l = ((left.max(0.0) * self.sample_rate as f32) as usize).min(max);
+		self.delay_r = ((right.max(0.0) * self.sample_rate as f32) as usize).min(max);
 	}
-}
\ No newline at end of file
+
+	fn process(&mut self, out: &mut [[f32; 2]]) {
+		let capacity = self.left.len();
+
+		for [l_sample, r_sample] in out {
+			let read_l = (self.write + capacity - self.delay_l) % capacity;
+			let read_r = (self.write + capacity - self.delay_r) % capacity;
+
+			let delayed_l = self.left[read_l];
+			let delayed_r = self.right[read_r];
+
+			let fb_l = delayed_l * (1.0 - self.cross_feed) + delayed_r * self.cross_feed;
+			let fb_r = delayed_r * (1.0 - self.cross_feed) + delayed_l * self.cross_feed;
+
+			self.left[self.write] = *l_sample + fb_l * self.feedback;
+			self.right[self.write] = *r_sample + fb_r * self.feedback;
+
+			self.write = (self.write + 1) % capacity;
+
+			*l_sample = *l_sample * (1.0 - self.wet) + delayed_l * self.wet;
+			*r_sample = *r_sample * (1.0 - self.wet) + delayed_r * self.wet;
+		}
+	}
+}